@@ -65,8 +65,10 @@
 //!
 //! ```
 //! # async fn foo() {
-//! let (traces_in, mut traces_out) = mick_jaeger::init(mick_jaeger::Config {
+//! let (traces_in, mut traces_out, _sampler_out) = mick_jaeger::init(mick_jaeger::Config {
 //!     service_name: "demo".to_string(),
+//!     sampler: mick_jaeger::Sampler::Probabilistic(1.0),
+//!     max_packet_size: 65_000,
 //! });
 //!
 //! let udp_socket = async_std::net::UdpSocket::bind("0.0.0.0:0").await.unwrap();
@@ -74,8 +76,9 @@
 //!
 //! async_std::task::spawn(async move {
 //!     loop {
-//!         let buf = traces_out.next().await;
-//!         udp_socket.send(&buf).await.unwrap();
+//!         for buf in traces_out.next().await {
+//!             udp_socket.send(&buf).await.unwrap();
+//!         }
 //!     }
 //! });
 //! # }
@@ -85,6 +88,14 @@
 //! slow, the spans sent on the [`TracesIn`] will be automatically and silently discarded. This
 //! isn't expected to happen under normal circumstances.
 //!
+//! If the local UDP agent isn't an option, [`init_http`] can be used instead of [`init`] to
+//! obtain a [`TracesOutHttp`], whose batches are meant to be POSTed directly to a Jaeger
+//! collector rather than sent as UDP datagrams.
+//!
+//! For deployments that front a Zipkin-compatible collector instead of a Jaeger one,
+//! [`init_zipkin`] returns a [`TracesOutZipkin`] that encodes batches as Zipkin v2 JSON, ready to
+//! be POSTed to `http://<host>:9411/api/v2/spans`.
+//!
 //! # Usage: spans
 //!
 //! Use the [`TracesIn::span`] method to create spans.
@@ -143,6 +154,7 @@
 
 use futures::{channel::mpsc, prelude::*, stream::FusedStream as _};
 use protocol::agent::TAgentSyncClient as _;
+use thrift::protocol::TOutputProtocol as _;
 use std::{
     convert::TryFrom as _,
     mem,
@@ -154,35 +166,125 @@ use thrift::transport::TIoChannel as _;
 
 mod glue;
 mod protocol;
+mod sampler;
+
+pub use sampler::{InvalidStrategy, Sampler, SamplerOut};
 
 /// Configuration to pass to [`init`].
 pub struct Config {
     /// Name of the service. Reported to the Jaeger server.
     pub service_name: String,
+    /// Strategy used to decide which traces are worth reporting.
+    pub sampler: Sampler,
+    /// Maximum size, in bytes, of a single datagram produced by [`TracesOut::next`].
+    ///
+    /// Should stay comfortably under the UDP datagram limit of the path towards the agent (e.g.
+    /// the MTU of a local loopback or Ethernet link), as anything larger risks being silently
+    /// dropped. Has no effect on [`TracesOutHttp`], whose batches are sent over TCP.
+    pub max_packet_size: usize,
 }
 
-pub fn init(config: Config) -> (Arc<TracesIn>, TracesOut) {
-    let (tx, rx) = mpsc::channel(256);
+/// Initializes the Jaeger client.
+///
+/// Returns a [`TracesOut`] that must be polled in order to send spans, as UDP datagrams, to the
+/// local Jaeger agent, and, if [`Config::sampler`] is [`Sampler::Remote`], a [`SamplerOut`] that
+/// must similarly be polled in order to keep the sampling strategy up to date.
+pub fn init(config: Config) -> (Arc<TracesIn>, TracesOut, Option<SamplerOut>) {
+    let (traces_in, rx) = new_traces_in(&config.sampler);
+
     let (buffer, write) = glue::TBufferChannel::with_capacity(512).split().unwrap();
     let client = protocol::agent::AgentSyncClient::new(
         thrift::protocol::TCompactInputProtocol::new(glue::TNoopChannel),
         thrift::protocol::TCompactOutputProtocol::new(write),
     );
+    let (size_probe_buffer, size_probe_write) =
+        glue::TBufferChannel::with_capacity(512).split().unwrap();
     let traces_out = TracesOut {
         rx: rx.ready_chunks(64),
-        process: protocol::jaeger::Process {
-            service_name: config.service_name,
-            tags: Some(vec![]),
-        },
+        process: new_process(&config.service_name),
+        max_packet_size: config.max_packet_size,
         buffer,
         client,
+        size_probe: (
+            size_probe_buffer,
+            thrift::protocol::TCompactOutputProtocol::new(size_probe_write),
+        ),
     };
-    let traces_in = TracesIn { sender: Mutex::new(tx) };
-    (Arc::new(traces_in), traces_out)
+
+    let sampler_out = new_sampler_out(&traces_in, config);
+    (traces_in, traces_out, sampler_out)
+}
+
+/// Like [`init`], but returns a [`TracesOutHttp`] instead of a [`TracesOut`].
+///
+/// This is meant for users who want to push batches directly to a Jaeger collector over HTTP
+/// (`http://<collector>:14268/api/traces`) rather than to the local UDP agent, for example
+/// because they need larger batches than fit in a UDP datagram, or because the agent isn't
+/// reachable from where the process runs.
+pub fn init_http(config: Config) -> (Arc<TracesIn>, TracesOutHttp, Option<SamplerOut>) {
+    let (traces_in, rx) = new_traces_in(&config.sampler);
+
+    let (buffer, write) = glue::TBufferChannel::with_capacity(512).split().unwrap();
+    let traces_out = TracesOutHttp {
+        rx: rx.ready_chunks(64),
+        process: new_process(&config.service_name),
+        buffer,
+        protocol: thrift::protocol::TBinaryOutputProtocol::new(write, true),
+    };
+
+    let sampler_out = new_sampler_out(&traces_in, config);
+    (traces_in, traces_out, sampler_out)
+}
+
+/// Like [`init`], but returns a [`TracesOutZipkin`] instead of a [`TracesOut`].
+///
+/// This is meant for deployments where the Jaeger agent is configured to front a
+/// Zipkin-compatible collector (e.g. via `COLLECTOR_ZIPKIN_HTTP_PORT`), so that spans can be
+/// POSTed directly to it as Zipkin v2 JSON without switching tracing libraries.
+pub fn init_zipkin(config: Config) -> (Arc<TracesIn>, TracesOutZipkin, Option<SamplerOut>) {
+    let (traces_in, rx) = new_traces_in(&config.sampler);
+
+    let traces_out = TracesOutZipkin {
+        rx: rx.ready_chunks(64),
+        local_endpoint_service_name: config.service_name.clone(),
+    };
+
+    let sampler_out = new_sampler_out(&traces_in, config);
+    (traces_in, traces_out, sampler_out)
+}
+
+fn new_traces_in(
+    sampler: &Sampler,
+) -> (Arc<TracesIn>, mpsc::Receiver<protocol::jaeger::Span>) {
+    let (tx, rx) = mpsc::channel(256);
+    let traces_in = Arc::new(TracesIn {
+        sender: Mutex::new(tx),
+        sampling: Mutex::new(sampler::ActiveStrategy::new(sampler)),
+    });
+    (traces_in, rx)
+}
+
+fn new_process(service_name: &str) -> protocol::jaeger::Process {
+    protocol::jaeger::Process {
+        service_name: service_name.to_string(),
+        tags: Some(vec![]),
+    }
+}
+
+fn new_sampler_out(traces_in: &Arc<TracesIn>, config: Config) -> Option<SamplerOut> {
+    match config.sampler {
+        Sampler::Remote { poll_interval, .. } => Some(SamplerOut {
+            traces_in: traces_in.clone(),
+            service_name: config.service_name,
+            poll_interval,
+        }),
+        Sampler::Probabilistic(_) | Sampler::RateLimiting(_) => None,
+    }
 }
 
 pub struct TracesIn {
     sender: Mutex<mpsc::Sender<protocol::jaeger::Span>>,
+    sampling: Mutex<sampler::ActiveStrategy>,
 }
 
 impl TracesIn {
@@ -190,18 +292,43 @@ impl TracesIn {
     ///
     /// Must be passed a `trace_id` that is used to group spans together. Its meaning is
     /// arbitrary.
+    ///
+    /// The sampling decision for the whole trace is taken here, by consulting the [`Sampler`]
+    /// passed to [`Config`], and is inherited by every child of the returned [`Span`].
     pub fn span(self: &Arc<Self>, trace_id: NonZeroU128, operation_name: impl Into<String>) -> Span {
+        let sampled = self.sampling.lock().unwrap().decide(trace_id.get());
+
         Span {
             traces_in: self.clone(),
             trace_id: trace_id.get(),
             span_id: rand::random(),
             parent_span_id: 0,
+            sampled,
             operation_name: operation_name.into(),
             start_time: SystemTime::now(),
             tags: base_tags(),
             logs: Vec::new(),
+            references: Vec::new(),
         }
     }
+
+    /// Builds a new [`Span`] that continues a trace whose parent span lives in another process.
+    ///
+    /// This is the distributed-tracing equivalent of [`Span::child`]: `parent_span_id` is the id
+    /// of a span generated by a different [`TracesIn`], typically received over the wire
+    /// alongside `trace_id`. The returned span records a [`ReferenceKind::ChildOf`] reference to
+    /// it, so that the Jaeger server can stitch the two processes' spans into a single trace.
+    pub fn span_with_parent(
+        self: &Arc<Self>,
+        trace_id: NonZeroU128,
+        parent_span_id: u64,
+        operation_name: impl Into<String>,
+    ) -> Span {
+        let mut span = self.span(trace_id, operation_name);
+        span.parent_span_id = parent_span_id;
+        span.add_reference(trace_id, parent_span_id, ReferenceKind::ChildOf);
+        span
+    }
 }
 
 pub struct Span {
@@ -210,10 +337,14 @@ pub struct Span {
     span_id: u64,
     /// [`Span::span_id`] of the parent, or `0` if no parent.
     parent_span_id: u64,
+    /// Whether this span's trace was picked by the [`Sampler`]. Inherited by all children, as
+    /// the decision is taken once for the whole trace.
+    sampled: bool,
     operation_name: String,
     start_time: SystemTime,
     tags: Vec<protocol::jaeger::Tag>,
     logs: Vec<protocol::jaeger::Log>,
+    references: Vec<protocol::jaeger::SpanRef>,
 }
 
 impl Span {
@@ -224,16 +355,37 @@ impl Span {
     /// >           children.
     // TODO: is this true? is this actually allowed?
     pub fn child(&self, operation_name: impl Into<String>) -> Span {
-        Span {
+        let mut span = Span {
             traces_in: self.traces_in.clone(),
             trace_id: self.trace_id,
             span_id: rand::random(),
             parent_span_id: self.span_id,
+            sampled: self.sampled,
             operation_name: operation_name.into(),
             start_time: SystemTime::now(),
             tags: base_tags(),
             logs: Vec::new(),
-        }
+            references: Vec::new(),
+        };
+        span.push_reference(self.trace_id, self.span_id, ReferenceKind::ChildOf);
+        span
+    }
+
+    /// Records a reference from this span to another one, which may belong to a different
+    /// process. Used for example by [`TracesIn::span_with_parent`] to continue a distributed
+    /// trace.
+    pub fn add_reference(&mut self, trace_id: NonZeroU128, span_id: u64, kind: ReferenceKind) {
+        self.push_reference(trace_id.get(), span_id, kind);
+    }
+
+    fn push_reference(&mut self, trace_id: u128, span_id: u64, kind: ReferenceKind) {
+        let (trace_id_low, trace_id_high) = split_trace_id(trace_id);
+        self.references.push(protocol::jaeger::SpanRef {
+            ref_type: kind.into_thrift(),
+            trace_id_low,
+            trace_id_high,
+            span_id: i64::from_ne_bytes(span_id.to_ne_bytes()),
+        });
     }
 
     /// Add a log entry to this span.
@@ -264,31 +416,59 @@ impl Span {
         // TODO: check for duplicates?
         self.tags.push(int_tag(key, value));
     }
+
+    /// Add a new key-value tag to this span.
+    pub fn add_bool_tag(&mut self, key: &str, value: bool) {
+        // TODO: check for duplicates?
+        self.tags.push(bool_tag(key, value));
+    }
+
+    /// Add a new key-value tag to this span.
+    pub fn add_double_tag(&mut self, key: &str, value: f64) {
+        // TODO: check for duplicates?
+        self.tags.push(double_tag(key, value));
+    }
+
+    /// Add a new key-value tag to this span.
+    pub fn add_binary_tag(&mut self, key: &str, value: &[u8]) {
+        // TODO: check for duplicates?
+        self.tags.push(binary_tag(key, value));
+    }
 }
 
 impl Drop for Span {
     fn drop(&mut self) {
+        // A non-sampled span is never reported, as there would be no point in sending it to a
+        // server configured to discard it anyway.
+        if !self.sampled {
+            return;
+        }
+
         let end_time = SystemTime::now();
 
         // Try to send the span, but don't try too hard. If the channel is full, drop the tracing
         // information.
+        let (trace_id_low, trace_id_high) = split_trace_id(self.trace_id);
+
         let _ = self
             .traces_in
             .sender
             .lock()
             .unwrap()
             .try_send(protocol::jaeger::Span {
-                trace_id_low: i64::from_ne_bytes(
-                    <[u8; 8]>::try_from(&self.trace_id.to_ne_bytes()[8..]).unwrap(),
-                ),
-                trace_id_high: i64::from_ne_bytes(
-                    <[u8; 8]>::try_from(&self.trace_id.to_ne_bytes()[..8]).unwrap(),
-                ),
+                trace_id_low,
+                trace_id_high,
                 span_id: i64::from_ne_bytes(self.span_id.to_ne_bytes()),
                 parent_span_id: i64::from_ne_bytes(self.parent_span_id.to_ne_bytes()),
                 operation_name: mem::replace(&mut self.operation_name, String::new()),
-                references: None,
-                flags: 0,
+                references: if self.references.is_empty() {
+                    None
+                } else {
+                    Some(mem::replace(&mut self.references, Vec::new()))
+                },
+                // Bit 0 is the "sampled" flag of the Jaeger protocol. As we never report
+                // non-sampled spans, this is always set here.
+                flags: 1,
                 start_time: i64::try_from(
                     self.start_time
                         .duration_since(SystemTime::UNIX_EPOCH)
@@ -332,7 +512,23 @@ impl<'a> Log<'a> {
         self
     }
 
-    // TODO: other methods
+    /// Add a new key-value tag to this log.
+    pub fn with_bool(mut self, key: &str, value: bool) -> Self {
+        self.fields.push(bool_tag(key, value));
+        self
+    }
+
+    /// Add a new key-value tag to this log.
+    pub fn with_double(mut self, key: &str, value: f64) -> Self {
+        self.fields.push(double_tag(key, value));
+        self
+    }
+
+    /// Add a new key-value tag to this log.
+    pub fn with_binary(mut self, key: &str, value: &[u8]) -> Self {
+        self.fields.push(binary_tag(key, value));
+        self
+    }
 }
 
 impl<'a> Drop for Log<'a> {
@@ -344,6 +540,32 @@ impl<'a> Drop for Log<'a> {
     }
 }
 
+/// Kind of relationship recorded by [`Span::add_reference`] between two spans.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ReferenceKind {
+    /// The referenced span is the parent of this one.
+    ChildOf,
+    /// This span follows the referenced one, without depending on its outcome.
+    FollowsFrom,
+}
+
+impl ReferenceKind {
+    fn into_thrift(self) -> protocol::jaeger::SpanRefType {
+        match self {
+            ReferenceKind::ChildOf => protocol::jaeger::SpanRefType::ChildOf,
+            ReferenceKind::FollowsFrom => protocol::jaeger::SpanRefType::FollowsFrom,
+        }
+    }
+}
+
+/// Splits a 128 bits trace id into its low and high halves, as expected by the Jaeger protocol.
+fn split_trace_id(trace_id: u128) -> (i64, i64) {
+    (
+        i64::from_ne_bytes((trace_id as u64).to_ne_bytes()),
+        i64::from_ne_bytes(((trace_id >> 64) as u64).to_ne_bytes()),
+    )
+}
+
 fn int_tag(key: &str, value: i64) -> protocol::jaeger::Tag {
     protocol::jaeger::Tag {
         key: key.to_string(),
@@ -368,6 +590,42 @@ fn string_tag(key: &str, value: &str) -> protocol::jaeger::Tag {
     }
 }
 
+fn bool_tag(key: &str, value: bool) -> protocol::jaeger::Tag {
+    protocol::jaeger::Tag {
+        key: key.to_string(),
+        v_type: protocol::jaeger::TagType::Bool,
+        v_bool: Some(value),
+        v_str: None,
+        v_long: None,
+        v_double: None,
+        v_binary: None,
+    }
+}
+
+fn double_tag(key: &str, value: f64) -> protocol::jaeger::Tag {
+    protocol::jaeger::Tag {
+        key: key.to_string(),
+        v_type: protocol::jaeger::TagType::Double,
+        v_double: Some(thrift::OrderedFloat(value)),
+        v_str: None,
+        v_long: None,
+        v_bool: None,
+        v_binary: None,
+    }
+}
+
+fn binary_tag(key: &str, value: &[u8]) -> protocol::jaeger::Tag {
+    protocol::jaeger::Tag {
+        key: key.to_string(),
+        v_type: protocol::jaeger::TagType::Binary,
+        v_binary: Some(value.to_vec()),
+        v_str: None,
+        v_long: None,
+        v_double: None,
+        v_bool: None,
+    }
+}
+
 fn base_tags() -> Vec<protocol::jaeger::Tag> {
     vec![
         string_tag("otel.library.name", env!("CARGO_PKG_NAME")),
@@ -381,6 +639,7 @@ fn base_tags() -> Vec<protocol::jaeger::Tag> {
 pub struct TracesOut {
     rx: stream::ReadyChunks<mpsc::Receiver<protocol::jaeger::Span>>,
     process: protocol::jaeger::Process,
+    max_packet_size: usize,
     buffer: thrift::transport::ReadHalf<glue::TBufferChannel>,
     client: protocol::agent::AgentSyncClient<
         thrift::protocol::TCompactInputProtocol<glue::TNoopChannel>,
@@ -388,11 +647,20 @@ pub struct TracesOut {
             thrift::transport::WriteHalf<glue::TBufferChannel>,
         >,
     >,
+    /// Scratch transport, entirely separate from `buffer`/`client`, used only to measure how
+    /// many bytes a span or an empty batch take up in the compact protocol.
+    size_probe: (
+        thrift::transport::ReadHalf<glue::TBufferChannel>,
+        thrift::protocol::TCompactOutputProtocol<thrift::transport::WriteHalf<glue::TBufferChannel>>,
+    ),
 }
 
 impl TracesOut {
-    /// Returns the next packet of data to send on the UDP socket.
-    pub async fn next(&mut self) -> Vec<u8> {
+    /// Returns the next packets of data to send on the UDP socket.
+    ///
+    /// Every returned packet is at most [`Config::max_packet_size`] bytes, unless a single span
+    /// alone exceeds that limit, in which case it is returned on its own and a warning is logged.
+    pub async fn next(&mut self) -> Vec<Vec<u8>> {
         if self.rx.is_terminated() {
             loop {
                 futures::pending!()
@@ -401,6 +669,48 @@ impl TracesOut {
 
         let spans = self.rx.select_next_some().await;
 
+        let overhead = self.process_overhead();
+        let mut packets = Vec::new();
+        let mut current = Vec::new();
+        let mut current_size = overhead;
+
+        for span in spans {
+            let span_size = self.span_len(&span);
+
+            if overhead + span_size > self.max_packet_size {
+                if !current.is_empty() {
+                    packets.push(self.emit_batch(mem::replace(&mut current, Vec::new())));
+                    current_size = overhead;
+                }
+                log::warn!(
+                    "span {:?} is {} bytes, which alone exceeds max_packet_size ({} bytes); \
+                     sending it in its own oversized datagram",
+                    span.operation_name,
+                    overhead + span_size,
+                    self.max_packet_size,
+                );
+                packets.push(self.emit_batch(vec![span]));
+                continue;
+            }
+
+            if current_size + span_size > self.max_packet_size {
+                packets.push(self.emit_batch(mem::replace(&mut current, Vec::new())));
+                current_size = overhead;
+            }
+
+            current_size += span_size;
+            current.push(span);
+        }
+
+        if !current.is_empty() {
+            packets.push(self.emit_batch(current));
+        }
+
+        packets
+    }
+
+    /// Serializes `spans` alongside the process header and returns the resulting datagram.
+    fn emit_batch(&mut self, spans: Vec<protocol::jaeger::Span>) -> Vec<u8> {
         self.client
             .emit_batch(protocol::jaeger::Batch {
                 spans,
@@ -410,6 +720,107 @@ impl TracesOut {
         self.buffer.take_bytes()
     }
 
+    /// Upper bound on the number of compact-protocol bytes that a datagram produced by
+    /// [`TracesOut::emit_batch`] takes on top of its spans: the `emitBatch` message envelope, the
+    /// process header and the header of the spans list.
+    fn process_overhead(&mut self) -> usize {
+        // The sequence number and the spans list length are varints, so they can be longer in a
+        // later datagram than in this empty one: allow 4 more bytes for the former (a 32-bit
+        // varint takes at most 5) and 1 more for the latter (a chunk holds at most 64 spans).
+        self.emit_batch(Vec::new()).len() + 4 + 1
+    }
+
+    /// Number of compact-protocol bytes taken by a single span.
+    fn span_len(&mut self, span: &protocol::jaeger::Span) -> usize {
+        span.write_to_out_protocol(&mut self.size_probe.1).unwrap();
+        self.size_probe.1.flush().unwrap();
+        self.size_probe.0.take_bytes().len()
+    }
+
+    /// Add a new key-value tag to the process.
+    pub fn add_string_tag(&mut self, key: &str, value: &str) {
+        // TODO: check for duplicates?
+        self.process
+            .tags
+            .as_mut()
+            .unwrap()
+            .push(string_tag(key, value));
+    }
+
+    /// Add a new key-value tag to the process.
+    pub fn add_int_tag(&mut self, key: &str, value: i64) {
+        // TODO: check for duplicates?
+        self.process
+            .tags
+            .as_mut()
+            .unwrap()
+            .push(int_tag(key, value));
+    }
+
+    /// Add a new key-value tag to the process.
+    pub fn add_bool_tag(&mut self, key: &str, value: bool) {
+        // TODO: check for duplicates?
+        self.process.tags.as_mut().unwrap().push(bool_tag(key, value));
+    }
+
+    /// Add a new key-value tag to the process.
+    pub fn add_double_tag(&mut self, key: &str, value: f64) {
+        // TODO: check for duplicates?
+        self.process
+            .tags
+            .as_mut()
+            .unwrap()
+            .push(double_tag(key, value));
+    }
+
+    /// Add a new key-value tag to the process.
+    pub fn add_binary_tag(&mut self, key: &str, value: &[u8]) {
+        // TODO: check for duplicates?
+        self.process
+            .tags
+            .as_mut()
+            .unwrap()
+            .push(binary_tag(key, value));
+    }
+}
+
+/// Receiving side for spans, returned by [`init_http`].
+///
+/// This object must be processed in order to POST traces to a Jaeger collector.
+pub struct TracesOutHttp {
+    rx: stream::ReadyChunks<mpsc::Receiver<protocol::jaeger::Span>>,
+    process: protocol::jaeger::Process,
+    buffer: thrift::transport::ReadHalf<glue::TBufferChannel>,
+    protocol: thrift::protocol::TBinaryOutputProtocol<thrift::transport::WriteHalf<glue::TBufferChannel>>,
+}
+
+impl TracesOutHttp {
+    /// Returns the next batch of data to POST to the collector.
+    ///
+    /// The returned [`HttpBatch`] holds the body. The path and content type expected by the
+    /// collector's HTTP API are [`HttpBatch::PATH_AND_QUERY`] and [`HttpBatch::CONTENT_TYPE`].
+    pub async fn next(&mut self) -> HttpBatch {
+        if self.rx.is_terminated() {
+            loop {
+                futures::pending!()
+            }
+        }
+
+        let spans = self.rx.select_next_some().await;
+
+        protocol::jaeger::Batch {
+            spans,
+            process: self.process.clone(),
+        }
+        .write_to_out_protocol(&mut self.protocol)
+        .unwrap();
+        self.protocol.flush().unwrap();
+
+        HttpBatch {
+            body: self.buffer.take_bytes(),
+        }
+    }
+
     /// Add a new key-value tag to the process.
     pub fn add_string_tag(&mut self, key: &str, value: &str) {
         // TODO: check for duplicates?
@@ -429,4 +840,347 @@ impl TracesOut {
             .unwrap()
             .push(int_tag(key, value));
     }
-}
\ No newline at end of file
+
+    /// Add a new key-value tag to the process.
+    pub fn add_bool_tag(&mut self, key: &str, value: bool) {
+        // TODO: check for duplicates?
+        self.process.tags.as_mut().unwrap().push(bool_tag(key, value));
+    }
+
+    /// Add a new key-value tag to the process.
+    pub fn add_double_tag(&mut self, key: &str, value: f64) {
+        // TODO: check for duplicates?
+        self.process
+            .tags
+            .as_mut()
+            .unwrap()
+            .push(double_tag(key, value));
+    }
+
+    /// Add a new key-value tag to the process.
+    pub fn add_binary_tag(&mut self, key: &str, value: &[u8]) {
+        // TODO: check for duplicates?
+        self.process
+            .tags
+            .as_mut()
+            .unwrap()
+            .push(binary_tag(key, value));
+    }
+}
+
+/// A single batch of spans, serialized with the Thrift binary protocol, ready to be POSTed to a
+/// Jaeger collector as returned by [`TracesOutHttp::next`].
+pub struct HttpBatch {
+    /// Body to send as the HTTP request payload.
+    pub body: Vec<u8>,
+}
+
+impl HttpBatch {
+    /// Path and query string the body must be POSTed to, e.g.
+    /// `http://<collector>:14268/api/traces?format=jaeger.thrift`.
+    pub const PATH_AND_QUERY: &'static str = "/api/traces?format=jaeger.thrift";
+
+    /// Value of the `Content-Type` header to send along the body.
+    pub const CONTENT_TYPE: &'static str = "application/vnd.apache.thrift.binary";
+}
+
+/// Receiving side for spans, returned by [`init_zipkin`].
+///
+/// This object must be processed in order to POST traces, as Zipkin v2 JSON, to a
+/// Zipkin-compatible collector.
+pub struct TracesOutZipkin {
+    rx: stream::ReadyChunks<mpsc::Receiver<protocol::jaeger::Span>>,
+    local_endpoint_service_name: String,
+}
+
+impl TracesOutZipkin {
+    /// Returns the next batch of spans, encoded as a Zipkin v2 JSON array, ready to be POSTed to
+    /// `http://<host>:9411/api/v2/spans`.
+    pub async fn next(&mut self) -> Vec<u8> {
+        if self.rx.is_terminated() {
+            loop {
+                futures::pending!()
+            }
+        }
+
+        let spans = self.rx.select_next_some().await;
+
+        let mut out = String::from("[");
+        for (index, span) in spans.iter().enumerate() {
+            if index != 0 {
+                out.push(',');
+            }
+            write_zipkin_span(&mut out, span, &self.local_endpoint_service_name);
+        }
+        out.push(']');
+        out.into_bytes()
+    }
+}
+
+/// Appends the Zipkin v2 JSON representation of `span` to `out`.
+fn write_zipkin_span(out: &mut String, span: &protocol::jaeger::Span, service_name: &str) {
+    use std::fmt::Write as _;
+
+    out.push('{');
+
+    let _ = write!(
+        out,
+        "\"traceId\":\"{:016x}{:016x}\",\"id\":\"{:016x}\",",
+        span.trace_id_high as u64, span.trace_id_low as u64, span.span_id as u64,
+    );
+    if span.parent_span_id != 0 {
+        let _ = write!(out, "\"parentId\":\"{:016x}\",", span.parent_span_id as u64);
+    }
+
+    out.push_str("\"name\":");
+    push_json_string(out, &span.operation_name);
+
+    let _ = write!(
+        out,
+        ",\"timestamp\":{},\"duration\":{},\"localEndpoint\":{{\"serviceName\":",
+        span.start_time, span.duration,
+    );
+    push_json_string(out, service_name);
+    out.push('}');
+
+    if let Some(tags) = &span.tags {
+        if !tags.is_empty() {
+            out.push_str(",\"tags\":{");
+            for (index, tag) in tags.iter().enumerate() {
+                if index != 0 {
+                    out.push(',');
+                }
+                push_json_string(out, &tag.key);
+                out.push(':');
+                push_json_string(out, &tag_value_to_string(tag));
+            }
+            out.push('}');
+        }
+    }
+
+    if let Some(logs) = &span.logs {
+        if !logs.is_empty() {
+            out.push_str(",\"annotations\":[");
+            for (index, log) in logs.iter().enumerate() {
+                if index != 0 {
+                    out.push(',');
+                }
+                let value = log
+                    .fields
+                    .iter()
+                    .map(|field| format!("{}={}", field.key, tag_value_to_string(field)))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                let _ = write!(out, "{{\"timestamp\":{},\"value\":", log.timestamp);
+                push_json_string(out, &value);
+                out.push('}');
+            }
+            out.push(']');
+        }
+    }
+
+    out.push('}');
+}
+
+/// Stringifies whichever of a [`protocol::jaeger::Tag`]'s value fields is set, as Zipkin only
+/// knows about string tag values.
+fn tag_value_to_string(tag: &protocol::jaeger::Tag) -> String {
+    if let Some(value) = &tag.v_str {
+        value.clone()
+    } else if let Some(value) = tag.v_long {
+        value.to_string()
+    } else if let Some(value) = tag.v_double {
+        value.0.to_string()
+    } else if let Some(value) = tag.v_bool {
+        value.to_string()
+    } else if let Some(value) = &tag.v_binary {
+        value.iter().map(|byte| format!("{:02x}", byte)).collect()
+    } else {
+        String::new()
+    }
+}
+
+/// Appends `s` to `out` as a quoted, escaped JSON string.
+fn push_json_string(out: &mut String, s: &str) {
+    use std::fmt::Write as _;
+
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod zipkin_tests {
+    use super::*;
+
+    fn sample_span() -> protocol::jaeger::Span {
+        protocol::jaeger::Span {
+            trace_id_low: 0x1122334455667788u64 as i64,
+            trace_id_high: 0x99aabbccddeeff00u64 as i64,
+            span_id: 0x0102030405060708u64 as i64,
+            parent_span_id: 0x0a0b0c0d0e0f1011u64 as i64,
+            operation_name: "op".to_string(),
+            references: None,
+            flags: 1,
+            start_time: 1_000,
+            duration: 500,
+            tags: Some(vec![
+                string_tag("weird", "he said \"hi\""),
+                bool_tag("ok", true),
+                double_tag("ratio", 0.5),
+                binary_tag("blob", &[0xde, 0xad]),
+            ]),
+            logs: Some(vec![protocol::jaeger::Log {
+                timestamp: 2_000,
+                fields: vec![string_tag("event", "tick")],
+            }]),
+        }
+    }
+
+    #[test]
+    fn write_zipkin_span_matches_expected_json() {
+        let span = sample_span();
+        let mut out = String::new();
+        write_zipkin_span(&mut out, &span, "svc");
+        assert_eq!(
+            out,
+            r#"{"traceId":"99aabbccddeeff001122334455667788","id":"0102030405060708","parentId":"0a0b0c0d0e0f1011","name":"op","timestamp":1000,"duration":500,"localEndpoint":{"serviceName":"svc"},"tags":{"weird":"he said \"hi\"","ok":"true","ratio":"0.5","blob":"dead"},"annotations":[{"timestamp":2000,"value":"event=tick"}]}"#
+        );
+    }
+
+    #[test]
+    fn write_zipkin_span_omits_parent_id_for_root_spans() {
+        let mut span = sample_span();
+        span.parent_span_id = 0;
+        let mut out = String::new();
+        write_zipkin_span(&mut out, &span, "svc");
+        assert!(!out.contains("parentId"));
+    }
+
+    #[test]
+    fn push_json_string_escapes_quotes_backslashes_and_control_characters() {
+        let mut out = String::new();
+        push_json_string(&mut out, "a\"b\\c\n\u{1}");
+        assert_eq!(out, r#""a\"b\\c\n\u0001""#);
+    }
+}
+#[cfg(test)]
+mod traces_out_tests {
+    use super::*;
+
+    /// Builds a [`TracesOut`] the same way [`init`] does, but with a caller-chosen
+    /// `max_packet_size` and its sender kept separate so tests can feed it spans directly.
+    fn test_traces_out(max_packet_size: usize) -> (mpsc::Sender<protocol::jaeger::Span>, TracesOut) {
+        let (tx, rx) = mpsc::channel(64);
+        let (buffer, write) = glue::TBufferChannel::with_capacity(512).split().unwrap();
+        let client = protocol::agent::AgentSyncClient::new(
+            thrift::protocol::TCompactInputProtocol::new(glue::TNoopChannel),
+            thrift::protocol::TCompactOutputProtocol::new(write),
+        );
+        let (size_probe_buffer, size_probe_write) =
+            glue::TBufferChannel::with_capacity(512).split().unwrap();
+        let traces_out = TracesOut {
+            rx: rx.ready_chunks(64),
+            process: new_process("test-service"),
+            max_packet_size,
+            buffer,
+            client,
+            size_probe: (
+                size_probe_buffer,
+                thrift::protocol::TCompactOutputProtocol::new(size_probe_write),
+            ),
+        };
+        (tx, traces_out)
+    }
+
+    fn test_span(operation_name: &str) -> protocol::jaeger::Span {
+        protocol::jaeger::Span {
+            trace_id_low: 1,
+            trace_id_high: 0,
+            span_id: 1,
+            parent_span_id: 0,
+            operation_name: operation_name.to_string(),
+            references: None,
+            flags: 1,
+            start_time: 0,
+            duration: 0,
+            tags: None,
+            logs: None,
+        }
+    }
+
+    #[test]
+    fn packets_stay_within_max_packet_size() {
+        async_std::task::block_on(async {
+            let (probe_tx, mut probe) = test_traces_out(usize::MAX);
+            let overhead = probe.process_overhead();
+            let span_size = probe.span_len(&test_span("s"));
+            drop(probe_tx);
+
+            // Only enough room for 3 spans per packet, so 10 identical spans must split.
+            let max_packet_size = overhead + span_size * 3;
+            let (mut tx, mut traces_out) = test_traces_out(max_packet_size);
+            for _ in 0..10 {
+                tx.try_send(test_span("s")).unwrap();
+            }
+            drop(tx);
+
+            let packets = traces_out.next().await;
+            assert!(
+                packets.len() > 1,
+                "expected spans to be split across multiple packets, got {}",
+                packets.len()
+            );
+            for packet in &packets {
+                assert!(packet.len() <= max_packet_size);
+            }
+        });
+    }
+
+    #[test]
+    fn oversized_span_is_emitted_alone_without_blocking_the_rest() {
+        async_std::task::block_on(async {
+            let (probe_tx, mut probe) = test_traces_out(usize::MAX);
+            let overhead = probe.process_overhead();
+            let normal_span_size = probe.span_len(&test_span("s"));
+            let big_operation_name = "x".repeat(4_096);
+            let big_span_size = probe.span_len(&test_span(&big_operation_name));
+            drop(probe_tx);
+
+            let max_packet_size = overhead + normal_span_size;
+            assert!(overhead + big_span_size > max_packet_size);
+
+            let (mut tx, mut traces_out) = test_traces_out(max_packet_size);
+            tx.try_send(test_span(&big_operation_name)).unwrap();
+            tx.try_send(test_span("s")).unwrap();
+            drop(tx);
+
+            let packets = traces_out.next().await;
+            assert_eq!(
+                packets.len(),
+                2,
+                "the oversized span should be emitted on its own, alongside the normal one"
+            );
+            assert!(
+                packets.iter().any(|p| p.len() > max_packet_size),
+                "the oversized span's own packet is allowed to exceed max_packet_size"
+            );
+            assert!(
+                packets.iter().any(|p| p.len() <= max_packet_size),
+                "the normal span should still fit within max_packet_size"
+            );
+        });
+    }
+}
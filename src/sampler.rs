@@ -0,0 +1,329 @@
+// Copyright (C) 2020 Pierre Krieger
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Sampling strategies.
+//!
+//! By default, a Jaeger agent expects its clients to only report a fraction of the traces they
+//! see, in order to keep the amount of data manageable. The [`Sampler`] passed to [`Config`] (see
+//! [`crate::Config`]) decides, for each new trace, whether its spans are actually worth sending.
+//!
+//! The decision is taken once, when the root [`Span`](crate::Span) of a trace is created, and is
+//! inherited by all of its children so that a trace is never partially reported.
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::TracesIn;
+
+/// Decides which traces should be reported to the Jaeger server.
+#[derive(Debug, Clone)]
+pub enum Sampler {
+    /// Report a fixed proportion of traces, in the range `0.0 ..= 1.0`.
+    ///
+    /// The decision is made deterministically from the trace id, so that all the spans of a
+    /// given trace always share the same fate, even across processes.
+    Probabilistic(f64),
+
+    /// Report at most the given number of new traces per second.
+    RateLimiting(f64),
+
+    /// Periodically query a Jaeger agent's sampling endpoint
+    /// (`http://<agent>:5778/sampling?service=<name>`) and use whatever strategy it returns.
+    ///
+    /// Querying the agent isn't done by this library. Use the [`SamplerOut`] returned by
+    /// [`crate::init`] to know when and what to query, similar to how [`TracesOut`](crate::TracesOut)
+    /// works for spans.
+    Remote {
+        /// Strategy to use before the first successful fetch.
+        initial: Box<Sampler>,
+        /// Delay between two fetches of the sampling strategy.
+        poll_interval: Duration,
+    },
+}
+
+/// Strategy currently being applied. Unlike [`Sampler`], this can't be [`Sampler::Remote`], as
+/// that variant is resolved into one of the other two as soon as a strategy has been fetched (or
+/// into its `initial` field while none has been fetched yet).
+pub(crate) enum ActiveStrategy {
+    Probabilistic(f64),
+    RateLimiting(TokenBucket),
+}
+
+impl ActiveStrategy {
+    pub(crate) fn new(sampler: &Sampler) -> Self {
+        match sampler {
+            Sampler::Probabilistic(rate) => ActiveStrategy::Probabilistic(*rate),
+            Sampler::RateLimiting(max_per_second) => {
+                ActiveStrategy::RateLimiting(TokenBucket::new(*max_per_second))
+            }
+            Sampler::Remote { initial, .. } => ActiveStrategy::new(initial),
+        }
+    }
+
+    /// Decides whether a trace with the given id should be sampled.
+    pub(crate) fn decide(&mut self, trace_id: u128) -> bool {
+        match self {
+            ActiveStrategy::Probabilistic(rate) => {
+                let trace_id_low = trace_id as u64;
+                (trace_id_low as f64 / u64::max_value() as f64) < *rate
+            }
+            ActiveStrategy::RateLimiting(bucket) => bucket.take(),
+        }
+    }
+}
+
+/// Simple token bucket, refilled at a constant rate, used to implement [`Sampler::RateLimiting`].
+pub(crate) struct TokenBucket {
+    max_per_second: f64,
+    balance: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(max_per_second: f64) -> Self {
+        TokenBucket {
+            max_per_second,
+            // Start with a full bucket, so that the first traces aren't needlessly dropped.
+            balance: max_per_second.max(1.0),
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Tries to take one token from the bucket. Returns `true` if one was available.
+    fn take(&mut self) -> bool {
+        // A rate of zero (or less) means "never sample". Letting it flow through the `.max(1.0)`
+        // clamp below like the legitimate sub-1 rates would still admit the initial full bucket.
+        if self.max_per_second <= 0.0 {
+            return false;
+        }
+
+        let now = Instant::now();
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.last_refill = now;
+
+        let capacity = self.max_per_second.max(1.0);
+        self.balance = (self.balance + elapsed * self.max_per_second).min(capacity);
+
+        if self.balance >= 1.0 {
+            self.balance -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Poller for the [`Sampler::Remote`] strategy, returned by [`crate::init`].
+///
+/// Must be polled in a loop, similarly to [`TracesOut`](crate::TracesOut). Each call to
+/// [`SamplerOut::next`] waits for the configured poll interval, then returns the path and query
+/// string that should be requested on the Jaeger agent. Once the HTTP response has been obtained, pass its body to
+/// [`SamplerOut::update`] in order to swap the strategy that newly-created spans will use.
+pub struct SamplerOut {
+    pub(crate) traces_in: Arc<TracesIn>,
+    pub(crate) service_name: String,
+    pub(crate) poll_interval: Duration,
+}
+
+impl SamplerOut {
+    /// Waits for the next poll to be due, then returns the path and query string to request on
+    /// the agent, e.g. `/sampling?service=foo`.
+    pub async fn next(&mut self) -> String {
+        futures_timer::Delay::new(self.poll_interval).await;
+        format!("/sampling?service={}", self.service_name)
+    }
+
+    /// Parses the JSON body returned by the agent's sampling endpoint and, if valid, makes it the
+    /// active sampling strategy.
+    pub fn update(&self, response_body: &str) -> Result<(), InvalidStrategy> {
+        let strategy = parse_strategy(response_body)?;
+        *self.traces_in.sampling.lock().unwrap() = strategy;
+        Ok(())
+    }
+}
+
+/// Error that can happen when parsing the response of the agent's sampling endpoint.
+#[derive(Debug, Clone)]
+pub struct InvalidStrategy;
+
+impl std::fmt::Display for InvalidStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "invalid or unsupported sampling strategy response")
+    }
+}
+
+impl std::error::Error for InvalidStrategy {}
+
+fn parse_strategy(body: &str) -> Result<ActiveStrategy, InvalidStrategy> {
+    if body.contains("\"PROBABILISTIC\"") {
+        let rate = extract_number(body, "\"samplingRate\"").ok_or(InvalidStrategy)?;
+        Ok(ActiveStrategy::Probabilistic(rate))
+    } else if body.contains("\"RATE_LIMITING\"") {
+        let max_per_second =
+            extract_number(body, "\"maxTracesPerSecond\"").ok_or(InvalidStrategy)?;
+        Ok(ActiveStrategy::RateLimiting(TokenBucket::new(max_per_second)))
+    } else {
+        Err(InvalidStrategy)
+    }
+}
+
+/// Finds `key` within `body` and parses the numeric value that follows its `:`.
+///
+/// This is a deliberately tiny ad-hoc parser rather than a full JSON implementation, as the only
+/// documents we ever need to read are the two fixed shapes returned by the agent.
+fn extract_number(body: &str, key: &str) -> Option<f64> {
+    let after_key = &body[body.find(key)? + key.len()..];
+    let after_colon = after_key.trim_start().strip_prefix(':')?.trim_start();
+    let end = after_colon
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+' || c == 'e'))
+        .unwrap_or(after_colon.len());
+    after_colon[..end].parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a [`TokenBucket`] with its fields set directly, bypassing [`TokenBucket::new`], so
+    /// that refills can be simulated without sleeping in the test.
+    fn bucket_with_balance(max_per_second: f64, balance: f64, elapsed_ago: Duration) -> TokenBucket {
+        TokenBucket {
+            max_per_second,
+            balance,
+            last_refill: Instant::now() - elapsed_ago,
+        }
+    }
+
+    #[test]
+    fn fresh_bucket_starts_full_and_drains() {
+        let mut bucket = TokenBucket::new(3.0);
+        assert!(bucket.take());
+        assert!(bucket.take());
+        assert!(bucket.take());
+        assert!(!bucket.take());
+    }
+
+    #[test]
+    fn bucket_refills_over_elapsed_time() {
+        let mut bucket = bucket_with_balance(2.0, 0.0, Duration::from_secs(1));
+        assert!(bucket.take());
+        assert!(bucket.take());
+        assert!(!bucket.take());
+    }
+
+    #[test]
+    fn bucket_does_not_refill_past_capacity() {
+        let mut bucket = bucket_with_balance(2.0, 0.0, Duration::from_secs(100));
+        assert!(bucket.take());
+        assert!(bucket.take());
+        assert!(!bucket.take());
+    }
+
+    #[test]
+    fn zero_rate_never_samples() {
+        // This is the regression that dcc2b73 fixed: without the `max_per_second <= 0.0` special
+        // case, the initial full bucket would let exactly one trace through.
+        let mut bucket = TokenBucket::new(0.0);
+        assert!(!bucket.take());
+        assert!(!bucket.take());
+    }
+
+    #[test]
+    fn negative_rate_never_samples() {
+        let mut bucket = TokenBucket::new(-1.0);
+        assert!(!bucket.take());
+    }
+
+    #[test]
+    fn sub_one_rate_does_not_starve_the_first_trace() {
+        let mut bucket = TokenBucket::new(0.2);
+        assert!(bucket.take());
+    }
+
+    #[test]
+    fn extract_number_parses_plain_integer() {
+        assert_eq!(
+            extract_number("{\"samplingRate\":1}", "\"samplingRate\""),
+            Some(1.0)
+        );
+    }
+
+    #[test]
+    fn extract_number_parses_fraction() {
+        assert_eq!(
+            extract_number("{\"samplingRate\":0.25}", "\"samplingRate\""),
+            Some(0.25)
+        );
+    }
+
+    #[test]
+    fn extract_number_parses_scientific_notation() {
+        assert_eq!(
+            extract_number("{\"maxTracesPerSecond\":1.5e2}", "\"maxTracesPerSecond\""),
+            Some(150.0)
+        );
+    }
+
+    #[test]
+    fn extract_number_ignores_trailing_garbage() {
+        assert_eq!(
+            extract_number("{\"maxTracesPerSecond\":10,\"extra\":true}", "\"maxTracesPerSecond\""),
+            Some(10.0)
+        );
+    }
+
+    #[test]
+    fn extract_number_missing_key_returns_none() {
+        assert_eq!(extract_number("{\"other\":1}", "\"samplingRate\""), None);
+    }
+
+    #[test]
+    fn extract_number_malformed_value_returns_none() {
+        assert_eq!(
+            extract_number("{\"samplingRate\":\"oops\"}", "\"samplingRate\""),
+            None
+        );
+    }
+
+    #[test]
+    fn parse_strategy_probabilistic() {
+        let strategy = parse_strategy(
+            r#"{"strategyType":"PROBABILISTIC","probabilisticSampling":{"samplingRate":0.5}}"#,
+        )
+        .unwrap();
+        assert!(matches!(strategy, ActiveStrategy::Probabilistic(rate) if rate == 0.5));
+    }
+
+    #[test]
+    fn parse_strategy_rate_limiting() {
+        let strategy = parse_strategy(
+            r#"{"strategyType":"RATE_LIMITING","rateLimitingSampling":{"maxTracesPerSecond":2}}"#,
+        )
+        .unwrap();
+        assert!(matches!(strategy, ActiveStrategy::RateLimiting(_)));
+    }
+
+    #[test]
+    fn parse_strategy_rejects_unknown_type() {
+        assert!(parse_strategy(r#"{"strategyType":"UNKNOWN"}"#).is_err());
+    }
+
+    #[test]
+    fn parse_strategy_rejects_missing_rate() {
+        assert!(parse_strategy(r#"{"strategyType":"PROBABILISTIC"}"#).is_err());
+    }
+}